@@ -1,4 +1,8 @@
-use std::fmt::{self, Write};
+use core::fmt::{self, Write};
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::boxed::Box;
 
 use num::bigint;
 
@@ -19,9 +23,46 @@ pub enum BertTerm {
     List(Vec<BertTerm>),
     Map(Vec<BertTerm>, Vec<BertTerm>),
     String(Vec<u8>),
-    Binary(Vec<u8>)
+    Binary(Vec<u8>),
+    Pid { node: Box<BertTerm>, id: u32, serial: u32, creation: u32 },
+    Port { node: Box<BertTerm>, id: u32, creation: u32 },
+    Reference { node: Box<BertTerm>, creation: u32, ids: Vec<u32> },
+    Fun {
+        module: Box<BertTerm>,
+        arity: u8,
+        uniq: [u8; 16],
+        index: u32,
+        old_index: Box<BertTerm>,
+        old_uniq: Box<BertTerm>,
+        pid: Box<BertTerm>,
+        free_vars: Vec<BertTerm>
+    },
+    // Legacy `FUN_EXT` (pre-R15, tag 117): unlike `NEW_FUN_EXT`, index
+    // and uniq aren't flattened into fixed-width fields, they're
+    // nested terms (ordinarily small integers) in their own right.
+    LegacyFun {
+        pid: Box<BertTerm>,
+        module: Box<BertTerm>,
+        index: Box<BertTerm>,
+        uniq: Box<BertTerm>,
+        free_vars: Vec<BertTerm>
+    },
+    Export { module: Box<BertTerm>, function: Box<BertTerm>, arity: Box<BertTerm> }
 }
 
+// No `Serialize`/`Deserialize` directly on `BertTerm`, by deliberate
+// choice rather than oversight: `Atom` only stores an offset/length
+// into the `Symtable` that was live when it was parsed, so a bare
+// `BertTerm` can never resolve its own atom text on its own. Giving it
+// a real impl would mean making `Atom` carry its own table handle
+// (e.g. an `Rc<Symtable>` per atom) or inlining atom text as an owned
+// `String`, either of which changes the term representation used by
+// every other module in this crate (`parser`, `encoder`, the printers,
+// `serde_impl` itself) for the sake of one generic-`Serialize`-field
+// use case. `serde_impl::to_term`/`from_bytes` are the supported path
+// for using `BertTerm` with serde instead: they carry the `Symtable`
+// alongside the term, so they can resolve atoms without this tradeoff.
+
 impl BertTerm {
     fn is_basic(&self) -> bool {
         match *self {
@@ -32,6 +73,12 @@ impl BertTerm {
             | BertTerm::String(_)
             | BertTerm::Binary(_)
             | BertTerm::Nil => true,
+            BertTerm::Pid { .. }
+            | BertTerm::Port { .. }
+            | BertTerm::Reference { .. }
+            | BertTerm::Fun { .. }
+            | BertTerm::LegacyFun { .. }
+            | BertTerm::Export { .. } => true,
             BertTerm::List(_)
             | BertTerm::Tuple(_)
             | BertTerm::Map(_, _) => false
@@ -80,7 +127,50 @@ impl <'a> PrettyPrinter<'a> {
             BertTerm::Binary(ref bytes) => self.write_string(bytes, f, "<<\"", "\">>"),
             BertTerm::List(ref terms) => self.write_collection(terms, f, depth, '[', ']'),
             BertTerm::Tuple(ref terms) => self.write_collection(terms, f, depth, '{', '}'),
-            BertTerm::Map(ref keys, ref vals) => self.write_map(keys, vals, f, depth)
+            BertTerm::Map(ref keys, ref vals) => self.write_map(keys, vals, f, depth),
+            BertTerm::Pid { ref node, id, serial, creation } => {
+                f.write_str("<pid ")?;
+                self.write_term(node, f, depth)?;
+                write!(f, ".{}.{}.{}>", id, serial, creation)
+            }
+            BertTerm::Port { ref node, id, creation } => {
+                f.write_str("#Port<")?;
+                self.write_term(node, f, depth)?;
+                write!(f, ".{}.{}>", id, creation)
+            }
+            BertTerm::Reference { ref node, creation, ref ids } => {
+                f.write_str("#Ref<")?;
+                self.write_term(node, f, depth)?;
+                for id in ids {
+                    write!(f, ".{}", id)?;
+                }
+                write!(f, ".{}>", creation)
+            }
+            BertTerm::Fun { ref module, index, ref uniq, .. } => {
+                f.write_str("#Fun<")?;
+                self.write_term(module, f, depth)?;
+                write!(f, ".{}.", index)?;
+                for b in uniq.iter() {
+                    write!(f, "{:02x}", b)?;
+                }
+                f.write_char('>')
+            }
+            BertTerm::LegacyFun { ref module, ref index, ref uniq, .. } => {
+                f.write_str("#Fun<")?;
+                self.write_term(module, f, depth)?;
+                f.write_char('.')?;
+                self.write_term(index, f, depth)?;
+                f.write_char('.')?;
+                self.write_term(uniq, f, depth)?;
+                f.write_char('>')
+            }
+            BertTerm::Export { ref module, ref function, ref arity } => {
+                self.write_term(module, f, depth)?;
+                f.write_char(':')?;
+                self.write_term(function, f, depth)?;
+                f.write_char('/')?;
+                self.write_term(arity, f, depth)
+            }
         }
     }
 
@@ -173,7 +263,7 @@ impl <'a> PrettyPrinter<'a> {
     }
 
     fn indentation(&self, depth: usize) -> String {
-        ::std::iter::once('\n')
+        ::core::iter::once('\n')
             .chain((0 .. depth * self.indent_width).map(|_| ' '))
             .collect()
     }
@@ -184,3 +274,201 @@ impl <'a> PrettyPrinter<'a> {
 fn is_printable(b: u8) -> bool {
     b >= 0x20 && b <= 0x7e
 }
+
+
+pub struct JsonPrinter<'a> {
+    term: &'a BertTerm,
+    indent_width: usize,
+    symtable: &'a Symtable
+}
+
+impl <'a> fmt::Display for JsonPrinter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_term(self.term, f, 0)
+    }
+}
+
+impl <'a> JsonPrinter<'a> {
+    /// Creates a JSON printer for `term`. When `indent_width` is 0,
+    /// the output is written on a single line; otherwise sub-terms
+    /// are indented with a width of `indent_width`.
+    pub fn new(term: &'a BertTerm, indent_width: usize, symtable: &'a Symtable) -> Self {
+        JsonPrinter { term, indent_width, symtable }
+    }
+
+    fn write_term(&self, term: &BertTerm, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        match *term {
+            BertTerm::Nil => f.write_str("[]"),
+            BertTerm::Int(n) => write!(f, "{}", n),
+            BertTerm::BigInt(ref n) => write!(f, "\"{}\"", n),
+            // JSON has no representation for NaN/Infinity; null is the
+            // closest standard stand-in.
+            BertTerm::Float(x) if !x.is_finite() => f.write_str("null"),
+            BertTerm::Float(x) => write!(f, "{}", x),
+            BertTerm::Atom { offset, length } => {
+                let s = self.symtable.get(offset, length);
+                self.write_json_str(s, f)
+            }
+            BertTerm::String(ref bytes) => self.write_json_string(bytes, f),
+            BertTerm::Binary(ref bytes) => write!(f, "\"{}\"", base64_encode(bytes)),
+            BertTerm::List(ref terms) => self.write_array(terms, f, depth),
+            BertTerm::Tuple(ref terms) => self.write_array(terms, f, depth),
+            BertTerm::Map(ref keys, ref vals) => self.write_object(keys, vals, f, depth),
+            // JSON has no native notion of a pid/port/reference/fun, so
+            // these render the same way `PrettyPrinter` would, quoted
+            // as a JSON string.
+            BertTerm::Pid { ref node, id, serial, creation } =>
+                self.write_json_str(
+                    &format!("<pid {}.{}.{}.{}>", self.atom_text(node), id, serial, creation), f),
+            BertTerm::Port { ref node, id, creation } =>
+                self.write_json_str(
+                    &format!("#Port<{}.{}.{}>", self.atom_text(node), id, creation), f),
+            BertTerm::Reference { ref node, creation, ref ids } => {
+                let ids: String = ids.iter().map(|id| format!(".{}", id)).collect();
+                self.write_json_str(
+                    &format!("#Ref<{}{}.{}>", self.atom_text(node), ids, creation), f)
+            }
+            BertTerm::Fun { ref module, index, ref uniq, .. } => {
+                let uniq: String = uniq.iter().map(|b| format!("{:02x}", b)).collect();
+                self.write_json_str(
+                    &format!("#Fun<{}.{}.{}>", self.atom_text(module), index, uniq), f)
+            }
+            BertTerm::LegacyFun { ref module, ref index, ref uniq, .. } =>
+                self.write_json_str(
+                    &format!("#Fun<{}.{}.{}>", self.atom_text(module), self.atom_text(index), self.atom_text(uniq)), f),
+            BertTerm::Export { ref module, ref function, ref arity } =>
+                self.write_json_str(
+                    &format!("{}:{}/{}", self.atom_text(module), self.atom_text(function), self.atom_text(arity)), f)
+        }
+    }
+
+    // Best-effort textual rendering of a term that is expected to be
+    // an atom or an integer (e.g. a pid's node name, an export's
+    // arity); falls back to "?" otherwise.
+    fn atom_text(&self, term: &BertTerm) -> String {
+        match *term {
+            BertTerm::Atom { offset, length } => self.symtable.get(offset, length).to_string(),
+            BertTerm::Int(n) => n.to_string(),
+            _ => "?".to_string()
+        }
+    }
+
+    fn write_array(&self, terms: &[BertTerm], f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        f.write_char('[')?;
+        let mut comma = "";
+        for t in terms {
+            f.write_str(comma)?;
+            self.write_newline_indent(f, depth + 1)?;
+            self.write_term(t, f, depth + 1)?;
+            comma = ",";
+        }
+        if !terms.is_empty() {
+            self.write_newline_indent(f, depth)?;
+        }
+        f.write_char(']')
+    }
+
+    fn write_object(&self,
+                     keys: &[BertTerm],
+                     vals: &[BertTerm],
+                     f: &mut fmt::Formatter,
+                     depth: usize) -> fmt::Result {
+        f.write_char('{')?;
+        let mut comma = "";
+        for i in 0 .. keys.len() {
+            f.write_str(comma)?;
+            self.write_newline_indent(f, depth + 1)?;
+            self.write_object_key(&keys[i], f)?;
+            f.write_str(": ")?;
+            self.write_term(&vals[i], f, depth + 1)?;
+            comma = ",";
+        }
+        if !keys.is_empty() {
+            self.write_newline_indent(f, depth)?;
+        }
+        f.write_char('}')
+    }
+
+    // JSON object keys must be strings; non-string BERT terms used
+    // as map keys are stringified the same way `Int`/`Float` values
+    // are rendered elsewhere, just quoted; compound keys are rendered
+    // as compact JSON text, which is then escaped as a JSON string.
+    fn write_object_key(&self, key: &BertTerm, f: &mut fmt::Formatter) -> fmt::Result {
+        match *key {
+            BertTerm::Atom { offset, length } =>
+                self.write_json_string(self.symtable.get(offset, length).as_bytes(), f),
+            BertTerm::String(ref bytes) => self.write_json_string(bytes, f),
+            BertTerm::Binary(ref bytes) => write!(f, "\"{}\"", base64_encode(bytes)),
+            BertTerm::Int(n) => write!(f, "\"{}\"", n),
+            BertTerm::BigInt(ref n) => write!(f, "\"{}\"", n),
+            BertTerm::Float(x) => write!(f, "\"{}\"", x),
+            BertTerm::Nil => f.write_str("\"[]\""),
+            ref other => {
+                let s = format!("{}", JsonPrinter::new(other, 0, self.symtable));
+                self.write_json_str(&s, f)
+            }
+        }
+    }
+
+    // Escapes raw, possibly-non-UTF-8 bytes (`String`/`Binary` terms);
+    // each byte becomes its own escape, so multi-byte UTF-8 sequences
+    // come out as one `\u00XX` per byte rather than the scalar value.
+    fn write_json_string(&self, bytes: &[u8], f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        for &b in bytes {
+            match b {
+                b'"' => f.write_str("\\\"")?,
+                b'\\' => f.write_str("\\\\")?,
+                0x20 ..= 0x7e => f.write_char(b as char)?,
+                _ => write!(f, "\\u{:04x}", b)?
+            }
+        }
+        f.write_char('"')
+    }
+
+    // Escapes a genuine `&str` (atoms, and the pid/port/ref/fun/export
+    // debug text) scalar-value by scalar-value, so multi-byte
+    // characters round-trip instead of being split into raw bytes.
+    fn write_json_str(&self, s: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        for c in s.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?
+            }
+        }
+        f.write_char('"')
+    }
+
+    fn write_newline_indent(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        if self.indent_width == 0 {
+            return Ok(());
+        }
+        f.write_char('\n')?;
+        for _ in 0 .. depth * self.indent_width {
+            f.write_char(' ')?;
+        }
+        Ok(())
+    }
+}
+
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = *chunk.get(1).unwrap_or(&0) as u32;
+        let b2 = *chunk.get(2).unwrap_or(&0) as u32;
+        let n = (b0 << 16) | (b1 << 8) | b2;
+        out.push(BASE64_ALPHABET[(n >> 18 & 0x3f) as usize] as char);
+        out.push(BASE64_ALPHABET[(n >> 12 & 0x3f) as usize] as char);
+        out.push(if chunk.len() > 1 { BASE64_ALPHABET[(n >> 6 & 0x3f) as usize] as char } else { '=' });
+        out.push(if chunk.len() > 2 { BASE64_ALPHABET[(n & 0x3f) as usize] as char } else { '=' });
+    }
+    out
+}
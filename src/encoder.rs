@@ -0,0 +1,305 @@
+use alloc::vec::Vec;
+
+use num::bigint::{self, Sign};
+
+#[cfg(feature = "std")]
+use std::io::Write;
+
+#[cfg(feature = "std")]
+use flate2::Compression;
+#[cfg(feature = "std")]
+use flate2::write::ZlibEncoder;
+
+use bertterm::BertTerm;
+use symtable::Symtable;
+
+const BERT_MAGIC_NUMBER: u8 = 131;
+#[cfg(feature = "std")]
+const COMPRESSED_TERM: u8 = 80;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const NEW_FLOAT_EXT: u8 = 70;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const MAP_EXT: u8 = 116;
+const NEW_PID_EXT: u8 = 88;
+const NEW_PORT_EXT: u8 = 89;
+const NEWER_REFERENCE_EXT: u8 = 90;
+const NEW_FUN_EXT: u8 = 112;
+const FUN_EXT: u8 = 117;
+const EXPORT_EXT: u8 = 113;
+
+/// Encodes a `BertTerm` into Erlang's External Term Format.
+///
+/// `Encoder` is the dual of `parser::Parser`: given a term produced by
+/// `Parser::parse` (and the symbol table that backs its atoms), it
+/// rebuilds the byte sequence that `Parser` consumed when that input
+/// used the encodings written here. Every term is written in one form
+/// only (UTF-8 atom tags, `NEW_FLOAT_EXT`, `SMALL_INTEGER_EXT` for
+/// 0..=255, `NEW_PID_EXT`, `NEW_PORT_EXT`, `NEWER_REFERENCE_EXT`, no
+/// compression), so input in legacy or compressed forms is not
+/// reproduced byte for byte.
+pub struct Encoder<'a> {
+    term: &'a BertTerm,
+    symtable: &'a Symtable,
+}
+
+impl <'a> Encoder<'a> {
+    pub fn new(term: &'a BertTerm, symtable: &'a Symtable) -> Self {
+        Encoder { term, symtable }
+    }
+
+    /// Encodes the term as a standalone `.bert` buffer: the magic
+    /// number followed by the term itself.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![BERT_MAGIC_NUMBER];
+        self.write_term(self.term, &mut buf);
+        buf
+    }
+
+    /// Encodes the term as a `.bert2`-framed chunk: a protobuf-style
+    /// varint byte length, as read by `Parser::parse_bert2`, followed
+    /// by the standalone `.bert` encoding.
+    pub fn to_bert2_bytes(&self) -> Vec<u8> {
+        let body = self.to_bytes();
+        let mut buf = Vec::with_capacity(body.len() + 5);
+        write_varint(body.len() as u64, &mut buf);
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    /// Encodes the term using the `compressed` wire format that
+    /// `erlang:term_to_binary(Term, [compressed])` produces: the
+    /// magic number, a `COMPRESSED_TERM` marker, a u32 uncompressed
+    /// size, then a zlib stream of the term bytes.
+    #[cfg(feature = "std")]
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        self.write_term(self.term, &mut body);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&body).expect("in-memory zlib compression cannot fail");
+        let compressed = zlib.finish().expect("in-memory zlib compression cannot fail");
+
+        let mut buf = Vec::with_capacity(compressed.len() + 6);
+        buf.push(BERT_MAGIC_NUMBER);
+        buf.push(COMPRESSED_TERM);
+        buf.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&compressed);
+        buf
+    }
+
+    fn write_term(&self, term: &BertTerm, buf: &mut Vec<u8>) {
+        match *term {
+            BertTerm::Nil => buf.push(NIL_EXT),
+            BertTerm::Int(n) => self.write_int(n, buf),
+            BertTerm::BigInt(ref n) => self.write_bigint(n, buf),
+            BertTerm::Float(x) => self.write_float(x, buf),
+            BertTerm::Atom { offset, length } => {
+                let s = self.symtable.get(offset, length);
+                self.write_atom(s, buf);
+            }
+            BertTerm::Tuple(ref terms) => self.write_tuple(terms, buf),
+            BertTerm::List(ref terms) => self.write_list(terms, buf),
+            BertTerm::Map(ref keys, ref vals) => self.write_map(keys, vals, buf),
+            BertTerm::String(ref bytes) => self.write_string(bytes, buf),
+            BertTerm::Binary(ref bytes) => self.write_binary(bytes, buf),
+            BertTerm::Pid { ref node, id, serial, creation } => {
+                buf.push(NEW_PID_EXT);
+                self.write_term(node, buf);
+                buf.extend_from_slice(&id.to_be_bytes());
+                buf.extend_from_slice(&serial.to_be_bytes());
+                buf.extend_from_slice(&creation.to_be_bytes());
+            }
+            BertTerm::Port { ref node, id, creation } => {
+                buf.push(NEW_PORT_EXT);
+                self.write_term(node, buf);
+                buf.extend_from_slice(&id.to_be_bytes());
+                buf.extend_from_slice(&creation.to_be_bytes());
+            }
+            BertTerm::Reference { ref node, creation, ref ids } => {
+                buf.push(NEWER_REFERENCE_EXT);
+                buf.extend_from_slice(&(ids.len() as u16).to_be_bytes());
+                self.write_term(node, buf);
+                buf.extend_from_slice(&creation.to_be_bytes());
+                for id in ids {
+                    buf.extend_from_slice(&id.to_be_bytes());
+                }
+            }
+            BertTerm::Fun { ref module, arity, ref uniq, index, ref old_index, ref old_uniq, ref pid, ref free_vars } => {
+                let mut body = Vec::new();
+                body.push(arity);
+                body.extend_from_slice(uniq);
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&(free_vars.len() as u32).to_be_bytes());
+                self.write_term(module, &mut body);
+                self.write_term(old_index, &mut body);
+                self.write_term(old_uniq, &mut body);
+                self.write_term(pid, &mut body);
+                for v in free_vars {
+                    self.write_term(v, &mut body);
+                }
+                buf.push(NEW_FUN_EXT);
+                // `size` counts itself (4 bytes) plus everything after it,
+                // i.e. not the tag byte.
+                buf.extend_from_slice(&((body.len() + 4) as u32).to_be_bytes());
+                buf.extend_from_slice(&body);
+            }
+            BertTerm::LegacyFun { ref pid, ref module, ref index, ref uniq, ref free_vars } => {
+                buf.push(FUN_EXT);
+                buf.extend_from_slice(&(free_vars.len() as u32).to_be_bytes());
+                self.write_term(pid, buf);
+                self.write_term(module, buf);
+                self.write_term(index, buf);
+                self.write_term(uniq, buf);
+                for v in free_vars {
+                    self.write_term(v, buf);
+                }
+            }
+            BertTerm::Export { ref module, ref function, ref arity } => {
+                buf.push(EXPORT_EXT);
+                self.write_term(module, buf);
+                self.write_term(function, buf);
+                self.write_term(arity, buf);
+            }
+        }
+    }
+
+    fn write_int(&self, n: i32, buf: &mut Vec<u8>) {
+        if n >= 0 && n <= 255 {
+            buf.push(SMALL_INTEGER_EXT);
+            buf.push(n as u8);
+        } else {
+            buf.push(INTEGER_EXT);
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+
+    fn write_float(&self, x: f64, buf: &mut Vec<u8>) {
+        buf.push(NEW_FLOAT_EXT);
+        buf.extend_from_slice(&x.to_bits().to_be_bytes());
+    }
+
+    fn write_atom(&self, s: &str, buf: &mut Vec<u8>) {
+        let bytes = s.as_bytes();
+        if bytes.len() <= 255 {
+            buf.push(SMALL_ATOM_UTF8_EXT);
+            buf.push(bytes.len() as u8);
+        } else {
+            buf.push(ATOM_UTF8_EXT);
+            buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        }
+        buf.extend_from_slice(bytes);
+    }
+
+    fn write_tuple(&self, terms: &[BertTerm], buf: &mut Vec<u8>) {
+        if terms.len() <= 255 {
+            buf.push(SMALL_TUPLE_EXT);
+            buf.push(terms.len() as u8);
+        } else {
+            buf.push(LARGE_TUPLE_EXT);
+            buf.extend_from_slice(&(terms.len() as u32).to_be_bytes());
+        }
+        for t in terms {
+            self.write_term(t, buf);
+        }
+    }
+
+    fn write_list(&self, terms: &[BertTerm], buf: &mut Vec<u8>) {
+        buf.push(LIST_EXT);
+        buf.extend_from_slice(&(terms.len() as u32).to_be_bytes());
+        for t in terms {
+            self.write_term(t, buf);
+        }
+        buf.push(NIL_EXT);
+    }
+
+    fn write_map(&self, keys: &[BertTerm], vals: &[BertTerm], buf: &mut Vec<u8>) {
+        buf.push(MAP_EXT);
+        buf.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+        for i in 0 .. keys.len() {
+            self.write_term(&keys[i], buf);
+            self.write_term(&vals[i], buf);
+        }
+    }
+
+    fn write_string(&self, bytes: &[u8], buf: &mut Vec<u8>) {
+        buf.push(STRING_EXT);
+        buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        buf.extend_from_slice(bytes);
+    }
+
+    fn write_binary(&self, bytes: &[u8], buf: &mut Vec<u8>) {
+        buf.push(BINARY_EXT);
+        buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        buf.extend_from_slice(bytes);
+    }
+
+    fn write_bigint(&self, n: &bigint::BigInt, buf: &mut Vec<u8>) {
+        let (sign, magnitude) = n.to_bytes_le();
+        let sign_byte = if sign == Sign::Minus { 1u8 } else { 0u8 };
+        if magnitude.len() <= 255 {
+            buf.push(SMALL_BIG_EXT);
+            buf.push(magnitude.len() as u8);
+        } else {
+            buf.push(LARGE_BIG_EXT);
+            buf.extend_from_slice(&(magnitude.len() as u32).to_be_bytes());
+        }
+        buf.push(sign_byte);
+        buf.extend_from_slice(&magnitude);
+    }
+}
+
+// https://developers.google.com/protocol-buffers/docs/encoding#varints
+fn write_varint(mut n: u64, buf: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            break;
+        } else {
+            buf.push(byte | 0x80);
+        }
+    }
+}
+
+
+#[test]
+fn test_write_varint() {
+    let mut buf = Vec::new();
+    write_varint(1, &mut buf);
+    assert_eq!(buf, vec![1]);
+
+    let mut buf = Vec::new();
+    write_varint(300, &mut buf);
+    assert_eq!(buf, vec![0b1010_1100, 0b0000_0010]);
+}
+
+#[test]
+fn test_round_trip_tuple() {
+    use parser::Parser;
+
+    let mut symtable = Symtable::new();
+    let (offset, length) = symtable.add("hello");
+    let term = BertTerm::Tuple(vec![
+        BertTerm::Int(42),
+        BertTerm::Atom { offset, length },
+        BertTerm::List(vec![BertTerm::Int(1), BertTerm::Int(2)]),
+    ]);
+    let bytes = Encoder::new(&term, &symtable).to_bytes();
+    assert_eq!(bytes[0], BERT_MAGIC_NUMBER);
+
+    let mut parser = Parser::new(bytes);
+    let parsed = parser.parse().unwrap();
+    assert_eq!(parsed, term);
+    assert_eq!(parser.symtable().get(offset, length), symtable.get(offset, length));
+}
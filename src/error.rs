@@ -0,0 +1,64 @@
+use core::fmt;
+
+use alloc::string::String;
+
+pub type Result<T> = ::core::result::Result<T, BertError>;
+
+#[derive(Debug)]
+pub enum BertError {
+    CannotOpenFile,
+    EOF(usize),
+    ExtraData(usize),
+    #[cfg(feature = "std")]
+    InvalidCompressedTerm(usize),
+    InvalidFloat(usize),
+    InvalidMagicNumber(usize),
+    InvalidTag(usize, u8),
+    InvalidUTF8Atom(usize),
+    #[cfg(feature = "std")]
+    Io(String),
+    VarintTooLarge(usize),
+}
+
+#[cfg(feature = "std")]
+impl From<::std::io::Error> for BertError {
+    fn from(e: ::std::io::Error) -> BertError {
+        BertError::Io(e.to_string())
+    }
+}
+
+impl fmt::Display for BertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BertError::CannotOpenFile =>
+                write!(f, "cannot open file"),
+            BertError::EOF(pos) =>
+                write!(f, "unexpected end of file at position {}", pos),
+            BertError::ExtraData(pos) =>
+                write!(f, "extra data found at position {}", pos),
+            #[cfg(feature = "std")]
+            BertError::InvalidCompressedTerm(pos) =>
+                write!(f, "invalid compressed term at position {}", pos),
+            BertError::InvalidFloat(pos) =>
+                write!(f, "invalid float at position {}", pos),
+            BertError::InvalidMagicNumber(pos) =>
+                write!(f, "invalid magic number at position {}", pos),
+            BertError::InvalidTag(pos, tag) =>
+                write!(f, "invalid tag {} at position {}", tag, pos),
+            BertError::InvalidUTF8Atom(pos) =>
+                write!(f, "invalid utf-8 atom at position {}", pos),
+            #[cfg(feature = "std")]
+            BertError::Io(ref msg) =>
+                write!(f, "I/O error: {}", msg),
+            BertError::VarintTooLarge(pos) =>
+                write!(f, "varint too large at position {}", pos),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for BertError {
+    fn description(&self) -> &str {
+        "error while parsing BERT term"
+    }
+}
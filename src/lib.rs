@@ -0,0 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// The `std` feature is on by default; building with `--no-default-features`
+// drops it, compiling `bertterm`/`parser`/`symtable`/`encoder` (the core
+// term model and codec) against `core` + `alloc` only. `stream` and the
+// `compressed`-term paths need real I/O and zlib, so they stay std-only.
+#[macro_use]
+extern crate alloc;
+extern crate num;
+
+// `no_std` builds get `core` injected automatically; with `std` on, pull it
+// in explicitly so `::core::…` paths shared between both builds still resolve.
+#[cfg(feature = "std")]
+extern crate core;
+
+#[cfg(feature = "std")]
+extern crate flate2;
+
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+
+pub mod bertterm;
+pub mod encoder;
+pub mod error;
+pub mod parser;
+pub mod symtable;
+
+#[cfg(feature = "std")]
+pub mod stream;
+
+#[cfg(feature = "serde")]
+pub mod serde_impl;
@@ -7,7 +7,8 @@ use std::fs::File;
 use clap::{Arg, App};
 
 use ppbert::parser;
-use ppbert::bertterm::BertTerm;
+use ppbert::stream::StreamParser;
+use ppbert::bertterm::{self, PrettyPrinter, JsonPrinter};
 use ppbert::error::{BertError, Result};
 
 fn main() {
@@ -18,24 +19,36 @@ fn main() {
         .arg(Arg::with_name("input_files")
              .value_name("BERT FILE")
              .multiple(true))
+        .arg(Arg::with_name("json")
+             .long("json")
+             .help("Print output as JSON instead of Erlang term syntax"))
+        .arg(Arg::with_name("bert2")
+             .long("bert2")
+             .help("Read a stream of concatenated, length-prefixed BERT terms (.bert2 format)"))
         .get_matches();
 
+    let json = matches.is_present("json");
+    let bert2 = matches.is_present("bert2");
+
     let files: Vec<&str> = match matches.values_of("input_files") {
         Some(files) => files.collect(),
         None => vec!["-"]
     };
 
     for file in files {
-        let _ = parse_and_print(file)
-            .map(|ref t| println!("{}", t))
-            .map_err(|ref e|
-                     writeln!(&mut io::stderr(), "ppbert: {}: {}", file, e)
-            );
+        let result = if bert2 {
+            parse_and_print_bert2(file, json)
+        } else {
+            parse_and_print(file, json)
+        };
+        let _ = result.map_err(|ref e|
+            writeln!(&mut io::stderr(), "ppbert: {}: {}", file, e)
+        );
     }
 }
 
 
-fn parse_and_print(file: &str) -> Result<BertTerm> {
+fn parse_and_print(file: &str, json: bool) -> Result<()> {
     let mut buf: Vec<u8> = Vec::new();
     if file == "-" {
         let mut stdin = io::stdin();
@@ -47,5 +60,40 @@ fn parse_and_print(file: &str) -> Result<BertTerm> {
             .map_err(|_| BertError::CannotOpenFile)?;
     }
     let mut parser = parser::Parser::new(buf);
-    return parser.parse();
+    let term = parser.parse()?;
+
+    if json {
+        println!("{}", JsonPrinter::new(&term, bertterm::DEFAULT_INDENT_WIDTH, parser.symtable()));
+    } else {
+        println!("{}", PrettyPrinter::new(&term,
+                                           bertterm::DEFAULT_INDENT_WIDTH,
+                                           bertterm::DEFAULT_MAX_TERMS_PER_LINE,
+                                           parser.symtable()));
+    }
+    Ok(())
+}
+
+
+// Unlike `parse_and_print`, this never buffers the whole input: terms
+// are printed one at a time as `StreamParser` reads them off the pipe.
+fn parse_and_print_bert2(file: &str, json: bool) -> Result<()> {
+    let reader: Box<dyn Read> = if file == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(file).map_err(|_| BertError::CannotOpenFile)?)
+    };
+
+    let mut terms = StreamParser::new(reader);
+    while let Some(term) = terms.next() {
+        let term = term?;
+        if json {
+            println!("{}", JsonPrinter::new(&term, bertterm::DEFAULT_INDENT_WIDTH, terms.symtable()));
+        } else {
+            println!("{}", PrettyPrinter::new(&term,
+                                               bertterm::DEFAULT_INDENT_WIDTH,
+                                               bertterm::DEFAULT_MAX_TERMS_PER_LINE,
+                                               terms.symtable()));
+        }
+    }
+    Ok(())
 }
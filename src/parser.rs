@@ -1,15 +1,35 @@
-use std::mem;
+use core::mem;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::boxed::Box;
 
 use num::bigint::{self, ToBigInt};
 use num::traits::{Zero, One};
 
-use encoding::{Encoding, DecoderTrap};
-use encoding::all::ISO_8859_1;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "std")]
+use flate2::read::ZlibDecoder;
 
 use bertterm::BertTerm;
 use error::{Result, BertError};
+use symtable::Symtable;
 
 const BERT_MAGIC_NUMBER: u8 = 131;
+#[cfg(feature = "std")]
+const COMPRESSED_TERM: u8 = 80;
+const NEW_PID_EXT: u8 = 88;
+const NEW_PORT_EXT: u8 = 89;
+const NEWER_REFERENCE_EXT: u8 = 90;
+const PID_EXT: u8 = 103;
+const PORT_EXT: u8 = 102;
+const REFERENCE_EXT: u8 = 101;
+const NEW_REFERENCE_EXT: u8 = 114;
+const EXPORT_EXT: u8 = 113;
+const NEW_FUN_EXT: u8 = 112;
+const FUN_EXT: u8 = 117;
 const SMALL_INTEGER_EXT: u8 = 97;
 const INTEGER_EXT: u8 = 98;
 const FLOAT_EXT: u8 = 99;
@@ -32,11 +52,31 @@ const MAP_EXT: u8 = 116;
 pub struct Parser {
     contents: Vec<u8>,
     pos: usize,
+    symtable: Symtable,
 }
 
 impl Parser {
     pub fn new(contents: Vec<u8>) -> Parser {
-        Parser { contents: contents, pos: 0 }
+        Parser::with_symtable(contents, Symtable::new())
+    }
+
+    /// Creates a parser that interns atoms into a pre-existing symbol
+    /// table instead of starting from an empty one; this is how
+    /// `StreamParser` keeps one `Symtable` shared across every term
+    /// of a multi-term stream.
+    pub fn with_symtable(contents: Vec<u8>, symtable: Symtable) -> Parser {
+        Parser { contents: contents, pos: 0, symtable: symtable }
+    }
+
+    /// The symbol table built up as atoms are parsed; atoms in the
+    /// returned `BertTerm`s are offsets into it.
+    pub fn symtable(&self) -> &Symtable {
+        &self.symtable
+    }
+
+    /// Reclaims the symbol table, consuming the parser.
+    pub fn into_symtable(self) -> Symtable {
+        self.symtable
     }
 
     pub fn parse(&mut self) -> Result<BertTerm> {
@@ -121,6 +161,20 @@ impl Parser {
             MAP_EXT => {
                 self.map()
             }
+            #[cfg(feature = "std")]
+            COMPRESSED_TERM => {
+                self.compressed_term(offset)
+            }
+            NEW_PID_EXT => { self.pid(true) }
+            PID_EXT => { self.pid(false) }
+            NEW_PORT_EXT => { self.port(true) }
+            PORT_EXT => { self.port(false) }
+            NEWER_REFERENCE_EXT => { self.new_reference(true) }
+            NEW_REFERENCE_EXT => { self.new_reference(false) }
+            REFERENCE_EXT => { self.reference() }
+            EXPORT_EXT => { self.export() }
+            NEW_FUN_EXT => { self.new_fun() }
+            FUN_EXT => { self.fun() }
             tag => { Err(BertError::InvalidTag(offset, tag)) }
         }
     }
@@ -158,7 +212,6 @@ impl Parser {
     }
 
     fn atom(&mut self, len: usize) -> Result<BertTerm> {
-        let offset = self.pos;
         let mut bytes: Vec<u8> = Vec::with_capacity(len);
         let mut is_ascii = true;
         for _ in 0 .. len {
@@ -176,11 +229,14 @@ impl Parser {
         // UTF-8 strings.
         if is_ascii {
             let s = unsafe { String::from_utf8_unchecked(bytes) };
-            Ok(BertTerm::Atom(s))
+            let (offset, length) = self.symtable.add(&s);
+            Ok(BertTerm::Atom { offset, length })
         } else {
-            ISO_8859_1.decode(&bytes, DecoderTrap::Strict)
-                .map(|s| BertTerm::Atom(s))
-                .map_err(|_| BertError::InvalidLatin1Atom(offset))
+            // Latin-1 code points map 1:1 onto the first 256 Unicode
+            // scalar values, so no decoding table is needed.
+            let s: String = bytes.iter().map(|&b| b as char).collect();
+            let (offset, length) = self.symtable.add(&s);
+            Ok(BertTerm::Atom { offset, length })
         }
     }
 
@@ -191,8 +247,11 @@ impl Parser {
             buf.push(self.eat_u8()?);
         }
         String::from_utf8(buf)
-            .map(|s| BertTerm::Atom(s))
             .map_err(|_| BertError::InvalidUTF8Atom(offset))
+            .map(|s| {
+                let (offset, length) = self.symtable.add(&s);
+                BertTerm::Atom { offset, length }
+            })
     }
 
     fn tuple(&mut self, len: usize) -> Result<BertTerm> {
@@ -263,6 +322,128 @@ impl Parser {
         Ok(BertTerm::Map(keys, vals))
     }
 
+    // `erlang:term_to_binary/2` with the `compressed` option produces
+    // `131, 80, <u32 uncompressed size>, <zlib stream>`; the zlib
+    // stream inflates to a bare term tag/value pair, with no magic
+    // number of its own (the outer `131` already covers the whole
+    // thing), hence `bert_term()` rather than `parse()` below.
+    #[cfg(feature = "std")]
+    fn compressed_term(&mut self, offset: usize) -> Result<BertTerm> {
+        let uncompressed_size = self.eat_u32_be()? as usize;
+        let mut decompressed = Vec::with_capacity(uncompressed_size);
+        {
+            let mut decoder = ZlibDecoder::new(&self.contents[self.pos ..]);
+            decoder.read_to_end(&mut decompressed)
+                .map_err(|_| BertError::InvalidCompressedTerm(offset))?;
+            self.pos += decoder.total_in() as usize;
+        }
+        if decompressed.len() != uncompressed_size {
+            return Err(BertError::InvalidCompressedTerm(offset));
+        }
+
+        let symtable = mem::replace(&mut self.symtable, Symtable::new());
+        let mut inner = Parser::with_symtable(decompressed, symtable);
+        let term = inner.bert_term()?;
+        self.symtable = inner.into_symtable();
+        Ok(term)
+    }
+
+    // `NEW_PID_EXT`'s creation is a u32, the legacy `PID_EXT`'s is a u8.
+    fn pid(&mut self, new: bool) -> Result<BertTerm> {
+        let node = self.bert_term()?;
+        let id = self.eat_u32_be()?;
+        let serial = self.eat_u32_be()?;
+        let creation = if new { self.eat_u32_be()? } else { self.eat_u8()? as u32 };
+        Ok(BertTerm::Pid { node: Box::new(node), id, serial, creation })
+    }
+
+    fn port(&mut self, new: bool) -> Result<BertTerm> {
+        let node = self.bert_term()?;
+        let id = self.eat_u32_be()?;
+        let creation = if new { self.eat_u32_be()? } else { self.eat_u8()? as u32 };
+        Ok(BertTerm::Port { node: Box::new(node), id, creation })
+    }
+
+    // Legacy `REFERENCE_EXT`: a single u32 id, u8 creation.
+    fn reference(&mut self) -> Result<BertTerm> {
+        let node = self.bert_term()?;
+        let id = self.eat_u32_be()?;
+        let creation = self.eat_u8()? as u32;
+        Ok(BertTerm::Reference { node: Box::new(node), creation, ids: vec![id] })
+    }
+
+    // `NEWER_REFERENCE_EXT`/`NEW_REFERENCE_EXT`: a u16 id-word count, then
+    // that many u32 id words; only the creation's width differs between
+    // the two.
+    fn new_reference(&mut self, newer: bool) -> Result<BertTerm> {
+        let len = self.eat_u16_be()? as usize;
+        let node = self.bert_term()?;
+        let creation = if newer { self.eat_u32_be()? } else { self.eat_u8()? as u32 };
+        let mut ids = Vec::with_capacity(len);
+        for _ in 0 .. len {
+            ids.push(self.eat_u32_be()?);
+        }
+        Ok(BertTerm::Reference { node: Box::new(node), creation, ids })
+    }
+
+    fn export(&mut self) -> Result<BertTerm> {
+        let module = self.bert_term()?;
+        let function = self.bert_term()?;
+        let arity = self.bert_term()?;
+        Ok(BertTerm::Export { module: Box::new(module), function: Box::new(function), arity: Box::new(arity) })
+    }
+
+    fn new_fun(&mut self) -> Result<BertTerm> {
+        let _size = self.eat_u32_be()?;
+        let arity = self.eat_u8()?;
+        let mut uniq = [0u8; 16];
+        for b in uniq.iter_mut() {
+            *b = self.eat_u8()?;
+        }
+        let index = self.eat_u32_be()?;
+        let num_free = self.eat_u32_be()? as usize;
+        let module = self.bert_term()?;
+        let old_index = self.bert_term()?;
+        let old_uniq = self.bert_term()?;
+        let pid = self.bert_term()?;
+        let mut free_vars = Vec::with_capacity(num_free);
+        for _ in 0 .. num_free {
+            free_vars.push(self.bert_term()?);
+        }
+        Ok(BertTerm::Fun {
+            module: Box::new(module),
+            arity,
+            uniq,
+            index,
+            old_index: Box::new(old_index),
+            old_uniq: Box::new(old_uniq),
+            pid: Box::new(pid),
+            free_vars,
+        })
+    }
+
+    // Legacy `FUN_EXT` (pre-R15): NumFree:32, then Pid, Module, Index,
+    // Uniq (each a nested term rather than `NEW_FUN_EXT`'s fixed-width
+    // fields), then NumFree free variable terms.
+    fn fun(&mut self) -> Result<BertTerm> {
+        let num_free = self.eat_u32_be()? as usize;
+        let pid = self.bert_term()?;
+        let module = self.bert_term()?;
+        let index = self.bert_term()?;
+        let uniq = self.bert_term()?;
+        let mut free_vars = Vec::with_capacity(num_free);
+        for _ in 0 .. num_free {
+            free_vars.push(self.bert_term()?);
+        }
+        Ok(BertTerm::LegacyFun {
+            pid: Box::new(pid),
+            module: Box::new(module),
+            index: Box::new(index),
+            uniq: Box::new(uniq),
+            free_vars,
+        })
+    }
+
     // Low-level parsing methods
     fn eof(&self) -> bool {
         self.pos >= self.contents.len()
@@ -344,8 +525,8 @@ impl Parser {
         }
 
         let mut x: u64 = 0;
-        for (i, byte) in bytes.iter().rev().enumerate() {
-            x = (x << (7*i) as u64) | (*byte as u64 & 0x7f);
+        for byte in bytes.iter().rev() {
+            x = (x << 7) | (*byte as u64 & 0x7f);
         }
 
         return Ok(x);
@@ -374,4 +555,9 @@ fn test_varint() {
                              0xff, 0xff, 0xff, 0x7f]).parse_varint().is_ok());
     assert!(Parser::new(vec![0xff, 0xff, 0xff, 0xff,
                              0xff, 0xff, 0xff, 0x80]).parse_varint().is_err());
+
+    // 3+ byte varints exercise every shift group, catching the
+    // off-by-shift-amount bug a 1-2 byte case can't.
+    assert_eq!(16384, Parser::new(vec![128, 128, 1]).parse_varint().unwrap());
+    assert_eq!(100000, Parser::new(vec![160, 141, 6]).parse_varint().unwrap());
 }
@@ -0,0 +1,466 @@
+//! Optional `serde` support, enabled by the `serde` feature.
+//!
+//! Rather than walking the byte stream directly, the `Serializer` maps
+//! serde's data model onto a `BertTerm` (reusing `encoder::Encoder` to
+//! turn that term into bytes), and the `Deserializer` walks a `BertTerm`
+//! produced by `parser::Parser` (reusing its `Symtable` to resolve
+//! atoms). This keeps both halves small and lets them piggyback on the
+//! round-tripping already guaranteed by `Encoder`/`Parser`.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::de::{self, Error as _, IntoDeserializer, Visitor};
+use serde::ser;
+use serde::{Deserialize, Serialize};
+
+use bertterm::BertTerm;
+use encoder::Encoder;
+use error::BertError;
+use parser::Parser;
+use symtable::Symtable;
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ::std::error::Error for Error {
+    fn description(&self) -> &str { &self.0 }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self { Error(msg.to_string()) }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self { Error(msg.to_string()) }
+}
+
+impl From<BertError> for Error {
+    fn from(e: BertError) -> Error { Error(e.to_string()) }
+}
+
+
+/// Serializes `value` to ETF bytes.
+pub fn to_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let (term, symtable) = to_term(value)?;
+    Ok(Encoder::new(&term, &symtable).to_bytes())
+}
+
+/// Serializes `value` into a `BertTerm` plus the symbol table backing
+/// its atoms (struct field names, enum variant names, ...).
+pub fn to_term<T: Serialize>(value: &T) -> Result<(BertTerm, Symtable), Error> {
+    let symtable = Rc::new(RefCell::new(Symtable::new()));
+    let term = value.serialize(TermSerializer { symtable: symtable.clone() })?;
+    let symtable = Rc::try_unwrap(symtable)
+        .map_err(|_| <Error as ser::Error>::custom("symbol table still shared after serialization"))?
+        .into_inner();
+    Ok((term, symtable))
+}
+
+/// Parses `bytes` as ETF and deserializes the resulting term into `T`.
+pub fn from_bytes<T>(bytes: Vec<u8>) -> Result<T, Error>
+    where T: for<'de> Deserialize<'de>
+{
+    let mut parser = Parser::new(bytes);
+    let term = parser.parse()?;
+    T::deserialize(BertTermDeserializer { term: &term, symtable: parser.symtable() })
+}
+
+
+// ---------------------------------------------------------------------
+// Serializer: serde data model -> BertTerm
+// ---------------------------------------------------------------------
+
+struct TermSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+}
+
+impl TermSerializer {
+    fn atom(&self, s: &str) -> BertTerm {
+        let (offset, length) = self.symtable.borrow_mut().add(s);
+        BertTerm::Atom { offset, length }
+    }
+}
+
+enum CollectKind { List, Tuple }
+
+struct CollectSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+    kind: CollectKind,
+    items: Vec<BertTerm>,
+}
+
+impl CollectSerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let term = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.items.push(term);
+        Ok(())
+    }
+
+    fn finish(self) -> BertTerm {
+        match self.kind {
+            CollectKind::List => BertTerm::List(self.items),
+            CollectKind::Tuple => BertTerm::Tuple(self.items),
+        }
+    }
+}
+
+impl ser::SerializeSeq for CollectSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+    fn end(self) -> Result<BertTerm, Error> { Ok(self.finish()) }
+}
+
+impl ser::SerializeTuple for CollectSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+    fn end(self) -> Result<BertTerm, Error> { Ok(self.finish()) }
+}
+
+impl ser::SerializeTupleStruct for CollectSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+    fn end(self) -> Result<BertTerm, Error> { Ok(self.finish()) }
+}
+
+struct TupleVariantSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+    tag: BertTerm,
+    items: Vec<BertTerm>,
+}
+
+impl ser::SerializeTupleVariant for TupleVariantSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let term = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.items.push(term);
+        Ok(())
+    }
+    fn end(self) -> Result<BertTerm, Error> {
+        Ok(BertTerm::Tuple(vec![self.tag, BertTerm::Tuple(self.items)]))
+    }
+}
+
+struct MapSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+    keys: Vec<BertTerm>,
+    vals: Vec<BertTerm>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let term = key.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.keys.push(term);
+        Ok(())
+    }
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let term = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.vals.push(term);
+        Ok(())
+    }
+    fn end(self) -> Result<BertTerm, Error> { Ok(BertTerm::Map(self.keys, self.vals)) }
+}
+
+struct StructSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+    keys: Vec<BertTerm>,
+    vals: Vec<BertTerm>,
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        let (offset, length) = self.symtable.borrow_mut().add(key);
+        self.keys.push(BertTerm::Atom { offset, length });
+        let term = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.vals.push(term);
+        Ok(())
+    }
+    fn end(self) -> Result<BertTerm, Error> { Ok(BertTerm::Map(self.keys, self.vals)) }
+}
+
+struct StructVariantSerializer {
+    symtable: Rc<RefCell<Symtable>>,
+    tag: BertTerm,
+    keys: Vec<BertTerm>,
+    vals: Vec<BertTerm>,
+}
+
+impl ser::SerializeStructVariant for StructVariantSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        let (offset, length) = self.symtable.borrow_mut().add(key);
+        self.keys.push(BertTerm::Atom { offset, length });
+        let term = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        self.vals.push(term);
+        Ok(())
+    }
+    fn end(self) -> Result<BertTerm, Error> {
+        Ok(BertTerm::Tuple(vec![self.tag, BertTerm::Map(self.keys, self.vals)]))
+    }
+}
+
+impl ser::Serializer for TermSerializer {
+    type Ok = BertTerm;
+    type Error = Error;
+    type SerializeSeq = CollectSerializer;
+    type SerializeTuple = CollectSerializer;
+    type SerializeTupleStruct = CollectSerializer;
+    type SerializeTupleVariant = TupleVariantSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructVariantSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<BertTerm, Error> {
+        Ok(self.atom(if v { "true" } else { "false" }))
+    }
+    fn serialize_i8(self, v: i8) -> Result<BertTerm, Error> { Ok(BertTerm::Int(i32::from(v))) }
+    fn serialize_i16(self, v: i16) -> Result<BertTerm, Error> { Ok(BertTerm::Int(i32::from(v))) }
+    fn serialize_i32(self, v: i32) -> Result<BertTerm, Error> { Ok(BertTerm::Int(v)) }
+    fn serialize_i64(self, v: i64) -> Result<BertTerm, Error> {
+        if v >= i64::from(i32::min_value()) && v <= i64::from(i32::max_value()) {
+            Ok(BertTerm::Int(v as i32))
+        } else {
+            Ok(BertTerm::BigInt(v.into()))
+        }
+    }
+    fn serialize_u8(self, v: u8) -> Result<BertTerm, Error> { Ok(BertTerm::Int(i32::from(v))) }
+    fn serialize_u16(self, v: u16) -> Result<BertTerm, Error> { Ok(BertTerm::Int(i32::from(v))) }
+    fn serialize_u32(self, v: u32) -> Result<BertTerm, Error> {
+        if v <= i32::max_value() as u32 {
+            Ok(BertTerm::Int(v as i32))
+        } else {
+            Ok(BertTerm::BigInt(v.into()))
+        }
+    }
+    fn serialize_u64(self, v: u64) -> Result<BertTerm, Error> {
+        if v <= i32::max_value() as u64 {
+            Ok(BertTerm::Int(v as i32))
+        } else {
+            Ok(BertTerm::BigInt(v.into()))
+        }
+    }
+    fn serialize_f32(self, v: f32) -> Result<BertTerm, Error> { Ok(BertTerm::Float(f64::from(v))) }
+    fn serialize_f64(self, v: f64) -> Result<BertTerm, Error> { Ok(BertTerm::Float(v)) }
+    fn serialize_char(self, v: char) -> Result<BertTerm, Error> {
+        let mut buf = [0u8; 4];
+        Ok(self.atom(v.encode_utf8(&mut buf)))
+    }
+    fn serialize_str(self, v: &str) -> Result<BertTerm, Error> { Ok(BertTerm::Binary(v.as_bytes().to_vec())) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<BertTerm, Error> { Ok(BertTerm::Binary(v.to_vec())) }
+    fn serialize_none(self) -> Result<BertTerm, Error> { Ok(self.atom("undefined")) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<BertTerm, Error> { value.serialize(self) }
+    fn serialize_unit(self) -> Result<BertTerm, Error> { Ok(BertTerm::Nil) }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<BertTerm, Error> { Ok(self.atom(name)) }
+    fn serialize_unit_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               variant: &'static str) -> Result<BertTerm, Error> {
+        Ok(self.atom(variant))
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                         _name: &'static str,
+                                                         value: &T) -> Result<BertTerm, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                          _name: &'static str,
+                                                          _index: u32,
+                                                          variant: &'static str,
+                                                          value: &T) -> Result<BertTerm, Error> {
+        let tag = self.atom(variant);
+        let payload = value.serialize(TermSerializer { symtable: self.symtable.clone() })?;
+        Ok(BertTerm::Tuple(vec![tag, payload]))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<CollectSerializer, Error> {
+        Ok(CollectSerializer { symtable: self.symtable, kind: CollectKind::List, items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<CollectSerializer, Error> {
+        Ok(CollectSerializer { symtable: self.symtable, kind: CollectKind::Tuple, items: Vec::with_capacity(len) })
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<CollectSerializer, Error> {
+        Ok(CollectSerializer { symtable: self.symtable, kind: CollectKind::Tuple, items: Vec::with_capacity(len) })
+    }
+    fn serialize_tuple_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                variant: &'static str,
+                                len: usize) -> Result<TupleVariantSerializer, Error> {
+        let tag = self.atom(variant);
+        Ok(TupleVariantSerializer { symtable: self.symtable, tag, items: Vec::with_capacity(len) })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer { symtable: self.symtable, keys: Vec::new(), vals: Vec::new() })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer { symtable: self.symtable, keys: Vec::with_capacity(len), vals: Vec::with_capacity(len) })
+    }
+    fn serialize_struct_variant(self,
+                                 _name: &'static str,
+                                 _index: u32,
+                                 variant: &'static str,
+                                 len: usize) -> Result<StructVariantSerializer, Error> {
+        let tag = self.atom(variant);
+        Ok(StructVariantSerializer { symtable: self.symtable, tag, keys: Vec::with_capacity(len), vals: Vec::with_capacity(len) })
+    }
+}
+
+
+// ---------------------------------------------------------------------
+// Deserializer: BertTerm -> serde data model
+// ---------------------------------------------------------------------
+
+const UNIT_PAYLOAD: BertTerm = BertTerm::Nil;
+
+struct BertTermDeserializer<'de> {
+    term: &'de BertTerm,
+    symtable: &'de Symtable,
+}
+
+struct SeqAccess<'de> {
+    terms: &'de [BertTerm],
+    symtable: &'de Symtable,
+    idx: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.idx >= self.terms.len() {
+            return Ok(None);
+        }
+        let term = &self.terms[self.idx];
+        self.idx += 1;
+        seed.deserialize(BertTermDeserializer { term, symtable: self.symtable }).map(Some)
+    }
+}
+
+struct MapAccess<'de> {
+    keys: &'de [BertTerm],
+    vals: &'de [BertTerm],
+    symtable: &'de Symtable,
+    idx: usize,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.idx >= self.keys.len() {
+            return Ok(None);
+        }
+        seed.deserialize(BertTermDeserializer { term: &self.keys[self.idx], symtable: self.symtable }).map(Some)
+    }
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let term = &self.vals[self.idx];
+        self.idx += 1;
+        seed.deserialize(BertTermDeserializer { term, symtable: self.symtable })
+    }
+}
+
+struct EnumAccess<'de> {
+    tag: &'de BertTerm,
+    payload: &'de BertTerm,
+    symtable: &'de Symtable,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        match *self.tag {
+            BertTerm::Atom { offset, length } => {
+                let name = self.symtable.get(offset, length);
+                let value = seed.deserialize::<de::value::StrDeserializer<Error>>(name.into_deserializer())?;
+                Ok((value, self))
+            }
+            _ => Err(Error::custom("expected an atom tag for an enum variant"))
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> { Ok(()) }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(BertTermDeserializer { term: self.payload, symtable: self.symtable })
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        match *self.payload {
+            BertTerm::Tuple(ref terms) => visitor.visit_seq(SeqAccess { terms, symtable: self.symtable, idx: 0 }),
+            _ => Err(Error::custom("expected a tuple enum payload"))
+        }
+    }
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match *self.payload {
+            BertTerm::Map(ref keys, ref vals) => visitor.visit_map(MapAccess { keys, vals, symtable: self.symtable, idx: 0 }),
+            _ => Err(Error::custom("expected a map enum payload"))
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for BertTermDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.term {
+            BertTerm::Nil => visitor.visit_seq(SeqAccess { terms: &[], symtable: self.symtable, idx: 0 }),
+            BertTerm::Int(n) => visitor.visit_i32(n),
+            BertTerm::BigInt(ref n) => visitor.visit_string(n.to_string()),
+            BertTerm::Float(x) => visitor.visit_f64(x),
+            BertTerm::Atom { offset, length } => visitor.visit_borrowed_str(self.symtable.get(offset, length)),
+            BertTerm::String(ref bytes) => visitor.visit_borrowed_bytes(bytes),
+            BertTerm::Binary(ref bytes) => visitor.visit_borrowed_bytes(bytes),
+            BertTerm::Tuple(ref terms) => visitor.visit_seq(SeqAccess { terms, symtable: self.symtable, idx: 0 }),
+            BertTerm::List(ref terms) => visitor.visit_seq(SeqAccess { terms, symtable: self.symtable, idx: 0 }),
+            BertTerm::Map(ref keys, ref vals) => visitor.visit_map(MapAccess { keys, vals, symtable: self.symtable, idx: 0 }),
+            // Pids/ports/references/funs have no generic-data shape;
+            // expose their `Debug` rendering instead of failing.
+            ref other => visitor.visit_string(format!("{:?}", other)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.term {
+            BertTerm::Atom { offset, length } if self.symtable.get(offset, length) == "undefined" =>
+                visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self,
+                                          _name: &'static str,
+                                          _variants: &'static [&'static str],
+                                          visitor: V) -> Result<V::Value, Error> {
+        match *self.term {
+            BertTerm::Atom { .. } => visitor.visit_enum(EnumAccess { tag: self.term, payload: &UNIT_PAYLOAD, symtable: self.symtable }),
+            BertTerm::Tuple(ref terms) if terms.len() == 2 =>
+                visitor.visit_enum(EnumAccess { tag: &terms[0], payload: &terms[1], symtable: self.symtable }),
+            _ => Err(Error::custom("expected an atom or a {atom, payload} tuple for an enum"))
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::Read;
+use std::mem;
+
+use bertterm::BertTerm;
+use error::{BertError, Result};
+use parser::Parser;
+use symtable::Symtable;
+
+const DEFAULT_BUF_CAPACITY: usize = 8192;
+
+/// Reads a `.bert2` stream incrementally from any `io::Read`, yielding
+/// one term at a time instead of requiring the whole stream to be
+/// buffered in memory up front the way `Parser::parse_bert2` does.
+/// Internally it keeps a small refill buffer that only grows as large
+/// as the biggest single frame requires, so memory stays bounded
+/// regardless of how many terms the stream contains.
+pub struct StreamParser<R> {
+    reader: R,
+    buf: Vec<u8>,
+    symtable: Symtable,
+    eof: bool,
+}
+
+impl <R: Read> StreamParser<R> {
+    pub fn new(reader: R) -> Self {
+        StreamParser {
+            reader: reader,
+            buf: Vec::with_capacity(DEFAULT_BUF_CAPACITY),
+            symtable: Symtable::new(),
+            eof: false,
+        }
+    }
+
+    /// The symbol table shared by every term yielded so far; atoms in
+    /// those terms are offsets into it.
+    pub fn symtable(&self) -> &Symtable {
+        &self.symtable
+    }
+
+    // Tops `buf` up to at least `n` bytes by reading from `reader`,
+    // short of actual eof. Returns the number of bytes available,
+    // which can be less than `n` if the stream ended early.
+    fn fill(&mut self, n: usize) -> Result<usize> {
+        let mut chunk = [0u8; DEFAULT_BUF_CAPACITY];
+        while self.buf.len() < n && !self.eof {
+            let read = self.reader.read(&mut chunk)?;
+            if read == 0 {
+                self.eof = true;
+            } else {
+                self.buf.extend_from_slice(&chunk[.. read]);
+            }
+        }
+        Ok(::std::cmp::min(self.buf.len(), n))
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        if self.fill(1)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.buf.remove(0)))
+    }
+
+    // https://developers.google.com/protocol-buffers/docs/encoding#varints
+    fn read_varint(&mut self) -> Result<Option<u64>> {
+        let mut bytes = Vec::with_capacity(mem::size_of::<u64>());
+        loop {
+            let b = match self.read_byte()? {
+                Some(b) => b,
+                None if bytes.is_empty() => return Ok(None),
+                None => return Err(BertError::EOF(0)),
+            };
+            bytes.push(b);
+            if b & 0x80 == 0 {
+                break;
+            }
+            if bytes.len() >= mem::size_of::<u64>() {
+                return Err(BertError::VarintTooLarge(0));
+            }
+        }
+
+        let mut x: u64 = 0;
+        for byte in bytes.iter().rev() {
+            x = (x << 7) | (*byte as u64 & 0x7f);
+        }
+        Ok(Some(x))
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>> {
+        if self.fill(len)? < len {
+            return Err(BertError::EOF(0));
+        }
+        Ok(self.buf.drain(.. len).collect())
+    }
+}
+
+impl <R: Read> Iterator for StreamParser<R> {
+    type Item = Result<BertTerm>;
+
+    fn next(&mut self) -> Option<Result<BertTerm>> {
+        let len = match self.read_varint() {
+            Ok(Some(len)) => len as usize,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let frame = match self.read_frame(len) {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let symtable = mem::replace(&mut self.symtable, Symtable::new());
+        let mut parser = Parser::with_symtable(frame, symtable);
+        let term = parser.parse();
+        self.symtable = parser.into_symtable();
+        Some(term)
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::string::String;
+
 const DEFAULT_CAPACITY: usize = 4096;
 
 /// A simple symbol table